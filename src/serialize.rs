@@ -0,0 +1,223 @@
+//! Versioned, streaming serialization for trees, roots, and proofs
+//!
+//! Every encoding here starts with a single version byte so that future changes to the wire
+//! format can be detected and rejected (or migrated) instead of silently misparsed. All
+//! variable-length fields are length-prefixed with a little-endian `u64`.
+
+use crate::{
+    consistency_proof::{ConsistencyProof, ConsistencyProofRef, VerificationError},
+    leaf::CanonicalSerialize,
+    merkle_tree::{CtMerkleTree, RootHash},
+};
+
+use std::io::{self, Read, Write};
+
+use digest::{typenum::Unsigned, Digest, Output};
+
+/// The current version of the on-disk/on-wire format produced by this module
+pub const SERIALIZATION_VERSION: u8 = 1;
+
+fn write_version(mut writer: impl Write) -> io::Result<()> {
+    writer.write_all(&[SERIALIZATION_VERSION])
+}
+
+fn read_version(mut reader: impl Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn check_version(version: u8) -> io::Result<()> {
+    if version != SERIALIZATION_VERSION {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported serialization version {}", version),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn write_bytes(mut writer: impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut len_buf = [0u8; 8];
+    reader.read_exact(&mut len_buf)?;
+    let len = u64::from_le_bytes(len_buf) as usize;
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Writes a [`ConsistencyProofRef`] to `writer`, prefixed with a version byte
+pub fn write_consistency_proof<H: Digest>(
+    proof: &ConsistencyProofRef<H>,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut writer = writer;
+    write_version(&mut writer)?;
+    write_bytes(&mut writer, proof.as_bytes())
+}
+
+/// Reads a [`ConsistencyProof`] previously written by [`write_consistency_proof`]
+pub fn read_consistency_proof<H: Digest>(reader: impl Read) -> io::Result<ConsistencyProof<H>> {
+    let mut reader = reader;
+    let version = read_version(&mut reader)?;
+    check_version(version)?;
+
+    let bytes = read_bytes(&mut reader)?;
+    ConsistencyProof::try_from_bytes(&bytes).map_err(|e| match e {
+        VerificationError::MalformedProof => {
+            io::Error::new(io::ErrorKind::InvalidData, "malformed consistency proof")
+        }
+        e => io::Error::new(io::ErrorKind::Other, e),
+    })
+}
+
+/// Writes a [`RootHash`] to `writer`, prefixed with a version byte
+pub fn write_root<H: Digest>(root: &RootHash<H>, writer: impl Write) -> io::Result<()> {
+    let mut writer = writer;
+    write_version(&mut writer)?;
+    writer.write_all(&root.num_leaves.to_le_bytes())?;
+    write_bytes(&mut writer, &root.root_hash)
+}
+
+/// Reads a [`RootHash`] previously written by [`write_root`]
+pub fn read_root<H: Digest>(reader: impl Read) -> io::Result<RootHash<H>> {
+    let mut reader = reader;
+    let version = read_version(&mut reader)?;
+    check_version(version)?;
+
+    let mut num_leaves_buf = [0u8; 8];
+    reader.read_exact(&mut num_leaves_buf)?;
+    let num_leaves = u64::from_le_bytes(num_leaves_buf);
+
+    let root_bytes = read_bytes(&mut reader)?;
+    if root_bytes.len() != H::OutputSize::USIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "root hash has the wrong length for this digest",
+        ));
+    }
+    let root_hash = Output::<H>::clone_from_slice(&root_bytes);
+
+    Ok(RootHash::new(root_hash, num_leaves))
+}
+
+/// Writes the internal node cache of a [`CtMerkleTree`] to `writer`, prefixed with a version
+/// byte. This does not serialize the tree's leaf values; the caller is expected to re-supply
+/// them (e.g. from their own canonical store) to [`read_tree`].
+pub fn write_tree<H, T>(tree: &CtMerkleTree<H, T>, writer: impl Write) -> io::Result<()>
+where
+    H: Digest,
+    T: CanonicalSerialize,
+{
+    let mut writer = writer;
+    write_version(&mut writer)?;
+    writer.write_all(&(tree.leaves.len() as u64).to_le_bytes())?;
+    writer.write_all(&(tree.internal_nodes.len() as u64).to_le_bytes())?;
+    for node in tree.internal_nodes.iter() {
+        write_bytes(&mut writer, node)?;
+    }
+    Ok(())
+}
+
+/// Reads back the internal node cache written by [`write_tree`], reattaching it to the given
+/// leaf values. Fails if `leaves.len()` does not match the leaf count recorded in the stream.
+pub fn read_tree<H, T>(reader: impl Read, leaves: Vec<T>) -> io::Result<CtMerkleTree<H, T>>
+where
+    H: Digest,
+    T: CanonicalSerialize,
+{
+    let mut reader = reader;
+    let version = read_version(&mut reader)?;
+    check_version(version)?;
+
+    let mut num_leaves_buf = [0u8; 8];
+    reader.read_exact(&mut num_leaves_buf)?;
+    let num_leaves = u64::from_le_bytes(num_leaves_buf) as usize;
+
+    if leaves.len() != num_leaves {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "number of supplied leaves does not match the serialized tree",
+        ));
+    }
+
+    let mut num_nodes_buf = [0u8; 8];
+    reader.read_exact(&mut num_nodes_buf)?;
+    let num_nodes = u64::from_le_bytes(num_nodes_buf) as usize;
+
+    let mut internal_nodes = Vec::with_capacity(num_nodes);
+    for _ in 0..num_nodes {
+        let node_bytes = read_bytes(&mut reader)?;
+        if node_bytes.len() != H::OutputSize::USIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "internal node has the wrong length for this digest",
+            ));
+        }
+        internal_nodes.push(Output::<H>::clone_from_slice(&node_bytes));
+    }
+
+    Ok(CtMerkleTree::from_parts(leaves, internal_nodes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merkle_tree::test::{rand_tree, rand_val};
+
+    use rand::thread_rng;
+
+    #[test]
+    fn consistency_proof_round_trips() {
+        let mut rng = thread_rng();
+        let mut tree = rand_tree(&mut rng, 12);
+        let old_root = tree.root();
+
+        for _ in 0..5 {
+            tree.push(rand_val(&mut rng)).unwrap();
+        }
+        let new_root = tree.root();
+        let proof = tree.consistency_proof(old_root.num_leaves);
+
+        let mut bytes = Vec::new();
+        write_consistency_proof(&proof.as_ref(), &mut bytes).unwrap();
+
+        let read_back = read_consistency_proof(&bytes[..]).unwrap();
+        new_root
+            .verify_consistency(&old_root, &read_back.as_ref())
+            .expect("round-tripped proof should still verify");
+    }
+
+    #[test]
+    fn root_round_trips() {
+        let mut rng = thread_rng();
+        let tree = rand_tree(&mut rng, 7);
+        let root = tree.root();
+
+        let mut bytes = Vec::new();
+        write_root(&root, &mut bytes).unwrap();
+
+        let read_back = read_root(&bytes[..]).unwrap();
+        assert_eq!(read_back.root_hash, root.root_hash);
+        assert_eq!(read_back.num_leaves, root.num_leaves);
+    }
+
+    #[test]
+    fn tree_round_trips() {
+        let mut rng = thread_rng();
+        let tree = rand_tree(&mut rng, 9);
+
+        let mut bytes = Vec::new();
+        write_tree(&tree, &mut bytes).unwrap();
+
+        let read_back = read_tree(&bytes[..], tree.leaves.clone()).unwrap();
+        assert_eq!(read_back.root().root_hash, tree.root().root_hash);
+    }
+}